@@ -0,0 +1,250 @@
+//! A typed client for Dialogflow CX (v3), the stateful counterpart to the ES (v2) client exposed
+//! as [`crate::Dialogflow`].
+//!
+//! CX agents are organized into flows and pages rather than a flat list of intents, and sessions
+//! are addressed as `projects/{id}/locations/{location}/agents/{agent}/sessions/{session}`
+//! instead of the ES `.../agent/sessions/{session}` path. [`DialogflowCx`] mirrors the shape of
+//! [`crate::Dialogflow`] while carrying the extra `location`/`agent_id` path segments and the
+//! session parameters CX expects to be threaded across turns.
+
+use crate::model::Parameters;
+use crate::{DetectIntentOptions, DialogflowError, SCOPES};
+use gcp_auth::AuthenticationManager;
+use serde::de::*;
+use serde_json::{Map, Value as JsonValue};
+
+/// An authenticated Dialogflow CX client
+pub struct DialogflowCx {
+    auth: AuthenticationManager,
+    client: reqwest::Client,
+    detect_intent_url: reqwest::Url,
+    options: DetectIntentOptions,
+    parameters: Map<String, JsonValue>,
+}
+
+impl DialogflowCx {
+    /// Initializes a Dialogflow CX client for the agent living at `location`/`agent_id`.
+    ///
+    /// Multiple strategies are used to authenticate the client, please refer to
+    /// [`gcp_auth`][gcp_auth::AuthenticationManager::new] for more information.
+    pub async fn new(location: &str, agent_id: &str) -> Result<Self, DialogflowError> {
+        let auth = gcp_auth::AuthenticationManager::new().await?;
+        let project_id = auth.project_id().await?;
+
+        Ok(Self {
+            auth,
+            client: reqwest::Client::new(),
+            detect_intent_url: format!(
+                "https://dialogflow.googleapis.com/v3/projects/{project_id}/locations/{location}/agents/{agent_id}/sessions/dev:detectIntent"
+            )
+            .parse()
+            .unwrap(),
+            options: Default::default(),
+            parameters: Map::new(),
+        })
+    }
+
+    pub fn with_detect_intent_options(
+        mut self,
+        detect_intent_options: DetectIntentOptions,
+    ) -> Self {
+        self.options = detect_intent_options;
+        self
+    }
+
+    /// Sets a session parameter. Every call to [`detect_intent_serde`][Self::detect_intent_serde]
+    /// merges the current session parameters into `queryParams.parameters`, and merges back
+    /// whatever the agent returned, since CX keeps session state on the server across turns.
+    pub fn set_parameter(&mut self, name: impl Into<String>, value: JsonValue) {
+        self.parameters.insert(name.into(), value);
+    }
+
+    pub fn parameters(&self) -> &Map<String, JsonValue> {
+        &self.parameters
+    }
+
+    /// Detects an intent and returns the result as a [`serde`]-deserialized enum, `I`, using the
+    /// same enum shape as [`Dialogflow::detect_intent_serde`][crate::Dialogflow::detect_intent_serde]
+    /// (the enum variant name must match the intent's display name).
+    pub async fn detect_intent_serde<I: DeserializeOwned>(
+        &mut self,
+        text: &str,
+    ) -> Result<I, DialogflowError> {
+        #[derive(serde::Serialize)]
+        struct Request<'a> {
+            query_input: QueryInput<'a>,
+            query_params: QueryParams<'a>,
+        }
+
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct QueryInput<'a> {
+            text: QueryInputText<'a>,
+            language_code: &'a language_tags::LanguageTag,
+        }
+
+        #[derive(serde::Serialize)]
+        struct QueryInputText<'a> {
+            text: &'a str,
+        }
+
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct QueryParams<'a> {
+            parameters: &'a Map<String, JsonValue>,
+        }
+
+        let req = Request {
+            query_input: QueryInput {
+                text: QueryInputText { text },
+                language_code: &self.options.language_code,
+            },
+            query_params: QueryParams {
+                parameters: &self.parameters,
+            },
+        };
+
+        let token = self
+            .auth
+            .get_token(SCOPES)
+            .await
+            .map_err(|_| DialogflowError::TokenNotAvailable)?;
+
+        let res = self
+            .client
+            .post(self.detect_intent_url.clone())
+            .header("Authorization", format!("Bearer {}", token.as_str()))
+            .json(&req)
+            .send()
+            .await?;
+
+        let res: CxDetectIntentResponse = res.json().await?;
+        let res = res.with_min_confidence(self.options.min_confidence);
+
+        if let JsonValue::Object(returned_parameters) = res.query_result.parameters.clone() {
+            self.parameters.extend(returned_parameters);
+        }
+
+        I::deserialize(res)
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CxDetectIntentResponse {
+    query_result: CxQueryResult,
+
+    #[serde(skip)]
+    min_confidence: Option<f64>,
+
+    #[serde(skip)]
+    variants: &'static [&'static str],
+}
+
+impl CxDetectIntentResponse {
+    /// Sets the confidence threshold below which [`EnumAccess::variant_seed`] falls back to the
+    /// `unknown` variant, as configured by `DetectIntentOptions::min_confidence`.
+    fn with_min_confidence(mut self, min_confidence: Option<f64>) -> Self {
+        self.min_confidence = min_confidence;
+        self
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CxQueryResult {
+    #[serde(default)]
+    parameters: JsonValue,
+
+    #[serde(rename = "match", default)]
+    intent_match: Option<CxMatch>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CxMatch {
+    intent: Option<CxIntent>,
+
+    #[serde(default)]
+    confidence: Option<f64>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CxIntent {
+    display_name: String,
+}
+
+impl<'de> Deserializer<'de> for CxDetectIntentResponse {
+    type Error = DialogflowError;
+
+    fn deserialize_any<V>(self, _visitor: V) -> Result<<V as Visitor<'de>>::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Self::Error::custom("this deserializer only supports enums"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option unit
+        unit_struct newtype_struct seq tuple tuple_struct map struct ignored_any identifier
+    }
+
+    fn deserialize_enum<V>(
+        mut self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<<V as Visitor<'de>>::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.variants = variants;
+        visitor.visit_enum(self)
+    }
+}
+
+impl<'de> EnumAccess<'de> for CxDetectIntentResponse {
+    type Error = DialogflowError;
+    type Variant = Parameters;
+
+    fn variant_seed<V>(
+        self,
+        seed: V,
+    ) -> Result<(<V as DeserializeSeed<'de>>::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let below_threshold = match (
+            self.min_confidence,
+            self.query_result.intent_match.as_ref().and_then(|m| m.confidence),
+        ) {
+            (Some(min_confidence), Some(confidence)) => confidence < min_confidence,
+            _ => false,
+        };
+
+        let display_name = self
+            .query_result
+            .intent_match
+            .filter(|_| !below_threshold)
+            .and_then(|m| m.intent)
+            .map(|i| i.display_name);
+
+        let variant_name = display_name.clone().unwrap_or_else(|| "unknown".to_string());
+
+        let intent_name = seed
+            .deserialize(IntoDeserializer::<DialogflowError>::into_deserializer(
+                variant_name.clone(),
+            ))
+            .map_err(|_| DialogflowError::Deserialize {
+                intent: display_name.clone(),
+                field: None,
+                message: format!(
+                    "intent `{variant_name}` does not match any of the expected variants {:?}",
+                    self.variants
+                ),
+            })?;
+
+        Ok((intent_name, Parameters::new(display_name, self.query_result.parameters)))
+    }
+}
@@ -2,11 +2,14 @@
 //!
 //! An easy-to-use typed [Google Dialogflow](https://dialogflow.cloud.google.com/) client.
 
+pub mod cx;
 pub mod model;
+pub mod session;
 
+use base64::Engine;
 use gcp_auth::AuthenticationManager;
 use language_tags::LanguageTag;
-use model::DetectIntentResponse;
+use model::{DetectIntentResponse, Fulfillment, Transcript};
 use reqwest::Url;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -24,13 +27,33 @@ pub enum DialogflowError {
     #[error("reqwest error: {0}")]
     Reqwest(#[from] reqwest::Error),
 
-    #[error("cannot deserialize JSON response")]
-    ResponseNotDeserializable,
+    #[error("cannot deserialize response (intent: {intent:?}, field: {field:?}): {message}")]
+    Deserialize {
+        /// The intent being matched or whose parameters were being parsed, if known.
+        intent: Option<String>,
+
+        /// The specific field that failed to deserialize, if it could be determined.
+        field: Option<String>,
+
+        message: String,
+    },
+}
+
+impl serde::de::Error for DialogflowError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Deserialize {
+            intent: None,
+            field: None,
+            message: msg.to_string(),
+        }
+    }
 }
 
 pub struct DetectIntentOptions {
     language_code: LanguageTag,
     geolocation: Option<(f32, f32)>,
+    output_audio_config: Option<OutputAudioConfig>,
+    min_confidence: Option<f64>,
 }
 
 impl Default for DetectIntentOptions {
@@ -38,14 +61,107 @@ impl Default for DetectIntentOptions {
         Self {
             language_code: LanguageTag::parse("en").unwrap(),
             geolocation: None,
+            output_audio_config: None,
+            min_confidence: None,
         }
     }
 }
 
+impl DetectIntentOptions {
+    /// Requests synthesized speech for the fulfillment response, returned by
+    /// [`Dialogflow::detect_intent_with_fulfillment`].
+    pub fn with_output_audio_config(mut self, output_audio_config: OutputAudioConfig) -> Self {
+        self.output_audio_config = Some(output_audio_config);
+        self
+    }
+
+    /// Intents detected with a confidence below this threshold are treated as the `unknown`
+    /// variant instead of the matched intent, since Dialogflow readily returns a weak match
+    /// rather than no match at all.
+    pub fn with_min_confidence(mut self, min_confidence: f64) -> Self {
+        self.min_confidence = Some(min_confidence);
+        self
+    }
+}
+
+/// The audio encoding of the raw bytes passed to
+/// [`Dialogflow::detect_intent_audio`], as understood by the Dialogflow API.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub enum AudioEncoding {
+    #[serde(rename = "AUDIO_ENCODING_LINEAR_16")]
+    Linear16,
+    #[serde(rename = "AUDIO_ENCODING_FLAC")]
+    Flac,
+    #[serde(rename = "AUDIO_ENCODING_MULAW")]
+    Mulaw,
+    #[serde(rename = "AUDIO_ENCODING_AMR")]
+    Amr,
+    #[serde(rename = "AUDIO_ENCODING_AMR_WB")]
+    AmrWb,
+    #[serde(rename = "AUDIO_ENCODING_OGG_OPUS")]
+    OggOpus,
+    #[serde(rename = "AUDIO_ENCODING_SPEEX_WITH_HEADER_BYTE")]
+    SpeexWithHeaderByte,
+}
+
+/// Describes the audio sent to [`Dialogflow::detect_intent_audio`].
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputAudioConfig {
+    pub audio_encoding: AudioEncoding,
+    pub sample_rate_hertz: u32,
+    pub language_code: LanguageTag,
+
+    /// Whether the response should include per-word timing and confidence in its
+    /// [`Transcript`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enable_word_info: Option<bool>,
+}
+
+/// The encoding to synthesize fulfillment speech as, understood by the Dialogflow API.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub enum OutputAudioEncoding {
+    #[serde(rename = "OUTPUT_AUDIO_ENCODING_LINEAR_16")]
+    Linear16,
+    #[serde(rename = "OUTPUT_AUDIO_ENCODING_MP3")]
+    Mp3,
+    #[serde(rename = "OUTPUT_AUDIO_ENCODING_OGG_OPUS")]
+    OggOpus,
+    #[serde(rename = "OUTPUT_AUDIO_ENCODING_MULAW")]
+    Mulaw,
+}
+
+/// The gender of the voice used to synthesize fulfillment speech.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub enum SsmlVoiceGender {
+    #[serde(rename = "SSML_VOICE_GENDER_MALE")]
+    Male,
+    #[serde(rename = "SSML_VOICE_GENDER_FEMALE")]
+    Female,
+    #[serde(rename = "SSML_VOICE_GENDER_NEUTRAL")]
+    Neutral,
+}
+
+/// Requests that Dialogflow synthesize the fulfillment response as speech, returned as
+/// [`Fulfillment::audio`] by [`Dialogflow::detect_intent_with_fulfillment`].
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputAudioConfig {
+    pub audio_encoding: OutputAudioEncoding,
+    pub sample_rate_hertz: u32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voice_name: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssml_gender: Option<SsmlVoiceGender>,
+}
+
 /// An authenticated Dialogflow client
 pub struct Dialogflow {
     auth: AuthenticationManager,
     client: reqwest::Client,
+    project_id: String,
     detect_intent_url: Url,
     options: DetectIntentOptions,
 }
@@ -63,10 +179,24 @@ impl Dialogflow {
             auth,
             client: reqwest::Client::new(),
             detect_intent_url: format!("https://dialogflow.googleapis.com/v2/projects/{project_id}/agent/sessions/dev:detectIntent").parse().unwrap(),
+            project_id,
             options: Default::default(),
         })
     }
 
+    /// Starts a [`Session`][session::Session] against this client, tracking active contexts
+    /// across turns instead of hitting the stateless `dev` session used by
+    /// [`detect_intent_serde`][Self::detect_intent_serde].
+    pub fn session(&self) -> session::Session<'_> {
+        session::Session::new(self)
+    }
+
+    /// Like [`session`][Self::session], but resumes (or starts) a specific session id instead of
+    /// generating a random one.
+    pub fn session_with_id(&self, session_id: impl Into<String>) -> session::Session<'_> {
+        session::Session::with_id(self, session_id)
+    }
+
     pub fn with_detect_intent_options(
         mut self,
         detect_intent_options: DetectIntentOptions,
@@ -187,11 +317,162 @@ impl Dialogflow {
             .send()
             .await?;
 
-        let res: DetectIntentResponse = res
-            .json()
+        let res: DetectIntentResponse = res.json().await?;
+        let res = res.with_min_confidence(self.options.min_confidence);
+
+        I::deserialize(res)
+    }
+
+    /// Detects an intent from raw audio, returning both the recognized intent (see
+    /// [`detect_intent_serde`][Self::detect_intent_serde] for how `I` should be shaped) and the
+    /// [`Transcript`] Dialogflow produced from the audio.
+    pub async fn detect_intent_audio<I: DeserializeOwned>(
+        &self,
+        audio: &[u8],
+        config: InputAudioConfig,
+    ) -> Result<(I, Transcript), DialogflowError> {
+        #[derive(serde::Serialize)]
+        struct Request<'a> {
+            query_input: QueryInput<'a>,
+            input_audio: String,
+            query_params: QueryParams,
+        }
+
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct QueryInput<'a> {
+            audio_config: &'a InputAudioConfig,
+        }
+
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct QueryParams {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            geo_location: Option<GeoLocation>,
+        }
+
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct GeoLocation<F: Serialize = f32> {
+            latitude: F,
+            longitude: F,
+        }
+
+        let req = Request {
+            query_input: QueryInput {
+                audio_config: &config,
+            },
+            input_audio: base64::engine::general_purpose::STANDARD.encode(audio),
+            query_params: QueryParams {
+                geo_location: self.options.geolocation.map(|g| GeoLocation {
+                    latitude: g.0,
+                    longitude: g.1,
+                }),
+            },
+        };
+
+        let token = self
+            .auth
+            .get_token(SCOPES)
+            .await
+            .map_err(|_| DialogflowError::TokenNotAvailable)?;
+
+        let res = self
+            .client
+            .post(self.detect_intent_url.clone())
+            .header("Authorization", format!("Bearer {}", token.as_str()))
+            .json(&req)
+            .send()
+            .await?;
+
+        let res: DetectIntentResponse = res.json().await?;
+        let res = res.with_min_confidence(self.options.min_confidence);
+
+        let transcript = res.transcript();
+        let intent = I::deserialize(res)?;
+
+        Ok((intent, transcript))
+    }
+
+    /// Like [`detect_intent_serde`][Self::detect_intent_serde], but also returns the
+    /// [`Fulfillment`] Dialogflow sent back (rich messages and, if
+    /// [`DetectIntentOptions::with_output_audio_config`] was used, synthesized speech).
+    pub async fn detect_intent_with_fulfillment<I: DeserializeOwned>(
+        &self,
+        text: &str,
+    ) -> Result<(I, Fulfillment), DialogflowError> {
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Request<'a> {
+            query_input: QueryInput<'a>,
+            query_params: QueryParams,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            output_audio_config: Option<&'a OutputAudioConfig>,
+        }
+
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct QueryInput<'a> {
+            text: QueryInputText<'a>,
+        }
+
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct QueryInputText<'a> {
+            language_code: &'a LanguageTag,
+            text: &'a str,
+        }
+
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct QueryParams {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            geo_location: Option<GeoLocation>,
+        }
+
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct GeoLocation<F: Serialize = f32> {
+            latitude: F,
+            longitude: F,
+        }
+
+        let req = Request {
+            query_input: QueryInput {
+                text: QueryInputText {
+                    language_code: &self.options.language_code,
+                    text,
+                },
+            },
+            query_params: QueryParams {
+                geo_location: self.options.geolocation.map(|g| GeoLocation {
+                    latitude: g.0,
+                    longitude: g.1,
+                }),
+            },
+            output_audio_config: self.options.output_audio_config.as_ref(),
+        };
+
+        let token = self
+            .auth
+            .get_token(SCOPES)
             .await
-            .map_err(|_| DialogflowError::ResponseNotDeserializable)?;
+            .map_err(|_| DialogflowError::TokenNotAvailable)?;
+
+        let res = self
+            .client
+            .post(self.detect_intent_url.clone())
+            .header("Authorization", format!("Bearer {}", token.as_str()))
+            .json(&req)
+            .send()
+            .await?;
+
+        let res: DetectIntentResponse = res.json().await?;
+        let res = res.with_min_confidence(self.options.min_confidence);
+
+        let fulfillment = res.fulfillment();
+        let intent = I::deserialize(res)?;
 
-        I::deserialize(res).map_err(|_| DialogflowError::ResponseNotDeserializable)
+        Ok((intent, fulfillment))
     }
 }
@@ -1,10 +1,22 @@
+use base64::Engine;
+use crate::DialogflowError;
 use serde::de::*;
 use serde_json::Value as JsonValue;
+use std::time::Duration;
 
 #[derive(Debug, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DetectIntentResponse {
     query_result: QueryResult,
+
+    #[serde(default)]
+    output_audio: Option<String>,
+
+    #[serde(skip)]
+    min_confidence: Option<f64>,
+
+    #[serde(skip)]
+    variants: &'static [&'static str],
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -17,6 +29,24 @@ struct QueryResult {
 
     #[serde(default)]
     pub intent_detection_confidence: Option<f64>,
+
+    #[serde(default)]
+    query_text: String,
+
+    #[serde(default)]
+    speech_word_info: Vec<SpeechWordInfo>,
+
+    #[serde(default)]
+    speech_recognition_confidence: Option<f32>,
+
+    #[serde(default)]
+    fulfillment_text: String,
+
+    #[serde(default)]
+    fulfillment_messages: Vec<RawFulfillmentMessage>,
+
+    #[serde(default)]
+    output_contexts: Vec<crate::session::Context>,
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -25,8 +55,209 @@ struct Intent {
     display_name: String,
 }
 
-impl<'de, 'a> Deserializer<'de> for DetectIntentResponse {
-    type Error = serde::de::value::Error;
+/// The transcription of an audio query, returned alongside the detected intent by
+/// [`Dialogflow::detect_intent_audio`][crate::Dialogflow::detect_intent_audio].
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    /// The text Dialogflow recognized from the audio input.
+    pub query_text: String,
+
+    /// The overall confidence that the speech was correctly recognized, between `0.0` and `1.0`.
+    pub confidence: Option<f32>,
+
+    /// Per-word recognition timing and confidence, present when `enable_word_info` was set on
+    /// the [`InputAudioConfig`][crate::InputAudioConfig].
+    pub words: Vec<SpeechWordInfo>,
+}
+
+/// Timing and confidence information for a single recognized word.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeechWordInfo {
+    pub word: String,
+
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub start_offset: Duration,
+
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub end_offset: Duration,
+
+    #[serde(default)]
+    pub confidence: f32,
+}
+
+/// Dialogflow encodes durations as strings such as `"1.200s"` rather than as numbers.
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = <&str>::deserialize(deserializer)?;
+    let secs = s
+        .strip_suffix('s')
+        .ok_or_else(|| D::Error::custom("expected a duration string ending in 's'"))?;
+
+    secs.parse::<f64>()
+        .map(Duration::from_secs_f64)
+        .map_err(D::Error::custom)
+}
+
+impl DetectIntentResponse {
+    /// Sets the confidence threshold below which [`EnumAccess::variant_seed`] falls back to the
+    /// `unknown` variant, as configured by `DetectIntentOptions::min_confidence`.
+    pub(crate) fn with_min_confidence(mut self, min_confidence: Option<f64>) -> Self {
+        self.min_confidence = min_confidence;
+        self
+    }
+
+    pub(crate) fn transcript(&self) -> Transcript {
+        Transcript {
+            query_text: self.query_result.query_text.clone(),
+            confidence: self.query_result.speech_recognition_confidence,
+            words: self.query_result.speech_word_info.clone(),
+        }
+    }
+
+    pub(crate) fn fulfillment(&self) -> Fulfillment {
+        Fulfillment {
+            text: self
+                .query_result
+                .fulfillment_text
+                .lines()
+                .map(String::from)
+                .collect(),
+            messages: self
+                .query_result
+                .fulfillment_messages
+                .iter()
+                .cloned()
+                .filter_map(FulfillmentMessage::from_raw)
+                .collect(),
+            audio: self
+                .output_audio
+                .as_deref()
+                .and_then(|base64| base64::engine::general_purpose::STANDARD.decode(base64).ok()),
+        }
+    }
+
+    /// The contexts active at the end of this turn, used by
+    /// [`Session`][crate::session::Session] to carry state across turns.
+    pub(crate) fn output_contexts(&self) -> Vec<crate::session::Context> {
+        self.query_result.output_contexts.clone()
+    }
+}
+
+/// The fulfillment Dialogflow sent back alongside the detected intent, returned by
+/// [`Dialogflow::detect_intent_with_fulfillment`][crate::Dialogflow::detect_intent_with_fulfillment].
+#[derive(Debug, Clone)]
+pub struct Fulfillment {
+    /// The plain-text fulfillment response, one entry per line of `fulfillmentText`.
+    pub text: Vec<String>,
+
+    /// The rich fulfillment messages (text, quick replies, cards, custom payloads).
+    pub messages: Vec<FulfillmentMessage>,
+
+    /// The synthesized speech audio, present when the request carried an `OutputAudioConfig`.
+    pub audio: Option<Vec<u8>>,
+}
+
+/// A single rich fulfillment message, as found in `queryResult.fulfillmentMessages`.
+#[derive(Debug, Clone)]
+pub enum FulfillmentMessage {
+    Text(Vec<String>),
+    QuickReplies {
+        title: Option<String>,
+        quick_replies: Vec<String>,
+    },
+    Card {
+        title: Option<String>,
+        subtitle: Option<String>,
+        image_uri: Option<String>,
+        buttons: Vec<CardButton>,
+    },
+    Payload(JsonValue),
+}
+
+impl FulfillmentMessage {
+    fn from_raw(raw: RawFulfillmentMessage) -> Option<Self> {
+        if let Some(text) = raw.text {
+            Some(Self::Text(text.text))
+        } else if let Some(quick_replies) = raw.quick_replies {
+            Some(Self::QuickReplies {
+                title: quick_replies.title,
+                quick_replies: quick_replies.quick_replies,
+            })
+        } else if let Some(card) = raw.card {
+            Some(Self::Card {
+                title: card.title,
+                subtitle: card.subtitle,
+                image_uri: card.image_uri,
+                buttons: card.buttons,
+            })
+        } else {
+            raw.payload.map(Self::Payload)
+        }
+    }
+}
+
+/// A button attached to a [`FulfillmentMessage::Card`].
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardButton {
+    pub text: String,
+
+    #[serde(default)]
+    pub postback: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawFulfillmentMessage {
+    #[serde(default)]
+    text: Option<RawText>,
+
+    #[serde(default)]
+    quick_replies: Option<RawQuickReplies>,
+
+    #[serde(default)]
+    card: Option<RawCard>,
+
+    #[serde(default)]
+    payload: Option<JsonValue>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+struct RawText {
+    text: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawQuickReplies {
+    #[serde(default)]
+    title: Option<String>,
+
+    #[serde(default)]
+    quick_replies: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawCard {
+    #[serde(default)]
+    title: Option<String>,
+
+    #[serde(default)]
+    subtitle: Option<String>,
+
+    #[serde(default)]
+    image_uri: Option<String>,
+
+    #[serde(default)]
+    buttons: Vec<CardButton>,
+}
+
+impl<'de> Deserializer<'de> for DetectIntentResponse {
+    type Error = DialogflowError;
 
     fn deserialize_any<V>(self, _visitor: V) -> Result<<V as Visitor<'de>>::Value, Self::Error>
     where
@@ -41,20 +272,21 @@ impl<'de, 'a> Deserializer<'de> for DetectIntentResponse {
     }
 
     fn deserialize_enum<V>(
-        self,
+        mut self,
         _name: &'static str,
-        _variants: &'static [&'static str],
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<<V as Visitor<'de>>::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
+        self.variants = variants;
         visitor.visit_enum(self)
     }
 }
 
 impl<'de> EnumAccess<'de> for DetectIntentResponse {
-    type Error = serde::de::value::Error;
+    type Error = DialogflowError;
     type Variant = Parameters;
 
     fn variant_seed<V>(
@@ -64,19 +296,56 @@ impl<'de> EnumAccess<'de> for DetectIntentResponse {
     where
         V: DeserializeSeed<'de>,
     {
-        let intent_name = match self.query_result.intent {
-            Some(i) => seed.deserialize(i.display_name.into_deserializer()),
-            None => seed.deserialize("unknown".into_deserializer()),
-        }?;
+        let below_threshold = match (self.min_confidence, self.query_result.intent_detection_confidence) {
+            (Some(min_confidence), Some(confidence)) => confidence < min_confidence,
+            _ => false,
+        };
+
+        let display_name = self
+            .query_result
+            .intent
+            .filter(|_| !below_threshold)
+            .map(|i| i.display_name);
 
-        Ok((intent_name, Parameters(self.query_result.parameters)))
+        let variant_name = display_name.clone().unwrap_or_else(|| "unknown".to_string());
+
+        let intent_name = seed
+            .deserialize(IntoDeserializer::<DialogflowError>::into_deserializer(
+                variant_name.clone(),
+            ))
+            .map_err(|_| DialogflowError::Deserialize {
+                intent: display_name.clone(),
+                field: None,
+                message: format!(
+                    "intent `{variant_name}` does not match any of the expected variants {:?}",
+                    self.variants
+                ),
+            })?;
+
+        Ok((
+            intent_name,
+            Parameters::new(display_name, self.query_result.parameters),
+        ))
     }
 }
 
-pub struct Parameters(JsonValue);
+pub struct Parameters {
+    intent: Option<String>,
+    value: JsonValue,
+}
 
-impl<'de, 'a> VariantAccess<'de> for Parameters {
-    type Error = serde::de::value::Error;
+impl Parameters {
+    /// Builds a [`VariantAccess`] over an arbitrary JSON object, so that other Dialogflow
+    /// surfaces (e.g. [`crate::cx`]) can reuse this struct-variant deserialization against their
+    /// own response shapes. `intent` names the variant being decoded, so that a failure can be
+    /// reported against it.
+    pub(crate) fn new(intent: Option<String>, value: JsonValue) -> Self {
+        Self { intent, value }
+    }
+}
+
+impl<'de> VariantAccess<'de> for Parameters {
+    type Error = DialogflowError;
 
     fn unit_variant(self) -> Result<(), Self::Error> {
         Ok(())
@@ -111,8 +380,23 @@ impl<'de, 'a> VariantAccess<'de> for Parameters {
     where
         V: Visitor<'de>,
     {
-        self.0
+        self.value
             .deserialize_struct("", fields, visitor)
-            .map_err(|err| serde::de::value::Error::custom(err))
+            .map_err(|err| DialogflowError::Deserialize {
+                intent: self.intent.clone(),
+                field: field_from_message(&err.to_string()),
+                message: err.to_string(),
+            })
     }
 }
+
+/// `serde_json`'s "missing field" errors name the offending field in backticks (e.g.
+/// `` missing field `location` ``), so pull it out in that one case. Other errors (e.g. a type
+/// mismatch) quote the offending *value* instead, with no field name available at all — reporting
+/// that backtick content as the field would just be wrong, so `None` is returned instead.
+fn field_from_message(message: &str) -> Option<String> {
+    let rest = message.strip_prefix("missing field ")?;
+    let start = rest.find('`')? + 1;
+    let end = start + rest[start..].find('`')?;
+    Some(rest[start..end].to_string())
+}
@@ -0,0 +1,154 @@
+//! Multi-turn session state for [`crate::Dialogflow`].
+//!
+//! [`Dialogflow::detect_intent_serde`][crate::Dialogflow::detect_intent_serde] always talks to
+//! the same hardcoded `dev` session and never carries contexts between calls, so every request is
+//! effectively stateless. [`Session`] instead owns a session id (generated or supplied by the
+//! caller) and the currently active [`Context`]s, threading them through
+//! `queryParams.contexts`/`queryResult.outputContexts` the way Dialogflow expects across turns.
+
+use crate::{Dialogflow, DialogflowError, SCOPES};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value as JsonValue};
+use uuid::Uuid;
+
+/// A Dialogflow context, either sent as an input context or received as an output context.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Context {
+    pub name: String,
+    pub lifespan_count: u32,
+
+    #[serde(default)]
+    pub parameters: Map<String, JsonValue>,
+}
+
+/// A stateful Dialogflow session, tracking active contexts across turns.
+///
+/// Create one with [`Dialogflow::session`] or [`Dialogflow::session_with_id`].
+pub struct Session<'a> {
+    dialogflow: &'a Dialogflow,
+    client: reqwest::Client,
+    session_id: String,
+    detect_intent_url: reqwest::Url,
+    contexts: Vec<Context>,
+}
+
+impl<'a> Session<'a> {
+    /// Starts a new session against `dialogflow`, with a randomly generated session id.
+    pub(crate) fn new(dialogflow: &'a Dialogflow) -> Self {
+        Self::with_id(dialogflow, Uuid::new_v4().to_string())
+    }
+
+    /// Resumes (or starts) the session identified by `session_id`.
+    pub(crate) fn with_id(dialogflow: &'a Dialogflow, session_id: impl Into<String>) -> Self {
+        let session_id = session_id.into();
+
+        let detect_intent_url = format!(
+            "https://dialogflow.googleapis.com/v2/projects/{}/agent/sessions/{session_id}:detectIntent",
+            dialogflow.project_id,
+        )
+        .parse()
+        .unwrap();
+
+        Self {
+            dialogflow,
+            client: dialogflow.client.clone(),
+            session_id,
+            detect_intent_url,
+            contexts: Vec::new(),
+        }
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// The contexts currently active in this session, as returned by the last turn.
+    pub fn contexts(&self) -> &[Context] {
+        &self.contexts
+    }
+
+    /// Detects an intent within this session, the same way as
+    /// [`Dialogflow::detect_intent_serde`], but also sends the session's active contexts and
+    /// updates them from the response's `outputContexts` for the next turn.
+    pub async fn detect_intent_serde<I: DeserializeOwned>(
+        &mut self,
+        text: &str,
+    ) -> Result<I, DialogflowError> {
+        #[derive(serde::Serialize)]
+        struct Request<'a> {
+            query_input: QueryInput<'a>,
+            query_params: QueryParams<'a>,
+        }
+
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct QueryInput<'a> {
+            text: QueryInputText<'a>,
+        }
+
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct QueryInputText<'a> {
+            language_code: &'a language_tags::LanguageTag,
+            text: &'a str,
+        }
+
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct QueryParams<'a> {
+            contexts: &'a [Context],
+            #[serde(skip_serializing_if = "Option::is_none")]
+            geo_location: Option<GeoLocation>,
+        }
+
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct GeoLocation {
+            latitude: f32,
+            longitude: f32,
+        }
+
+        let req = Request {
+            query_input: QueryInput {
+                text: QueryInputText {
+                    language_code: &self.dialogflow.options.language_code,
+                    text,
+                },
+            },
+            query_params: QueryParams {
+                contexts: &self.contexts,
+                geo_location: self.dialogflow.options.geolocation.map(|g| GeoLocation {
+                    latitude: g.0,
+                    longitude: g.1,
+                }),
+            },
+        };
+
+        let token = self
+            .dialogflow
+            .auth
+            .get_token(SCOPES)
+            .await
+            .map_err(|_| DialogflowError::TokenNotAvailable)?;
+
+        let res = self
+            .client
+            .post(self.detect_intent_url.clone())
+            .header("Authorization", format!("Bearer {}", token.as_str()))
+            .json(&req)
+            .send()
+            .await?;
+
+        let res: crate::model::DetectIntentResponse = res.json().await?;
+        let res = res.with_min_confidence(self.dialogflow.options.min_confidence);
+
+        self.contexts = res
+            .output_contexts()
+            .into_iter()
+            .filter(|context| context.lifespan_count > 0)
+            .collect();
+
+        I::deserialize(res)
+    }
+}